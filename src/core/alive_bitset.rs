@@ -0,0 +1,174 @@
+use std::io::{self, Write};
+use directory::ReadOnlySource;
+
+fn write_u32(write: &mut Write, value: u32) -> io::Result<()> {
+    let bytes = [
+        (value & 0xff) as u8,
+        ((value >> 8) & 0xff) as u8,
+        ((value >> 16) & 0xff) as u8,
+        ((value >> 24) & 0xff) as u8,
+    ];
+    write.write_all(&bytes)
+}
+
+fn read_u32(data: &[u8]) -> u32 {
+    (data[0] as u32)
+        | ((data[1] as u32) << 8)
+        | ((data[2] as u32) << 16)
+        | ((data[3] as u32) << 24)
+}
+
+fn write_u64(write: &mut Write, value: u64) -> io::Result<()> {
+    let bytes: [u8; 8] = [
+        (value & 0xff) as u8,
+        ((value >> 8) & 0xff) as u8,
+        ((value >> 16) & 0xff) as u8,
+        ((value >> 24) & 0xff) as u8,
+        ((value >> 32) & 0xff) as u8,
+        ((value >> 40) & 0xff) as u8,
+        ((value >> 48) & 0xff) as u8,
+        ((value >> 56) & 0xff) as u8,
+    ];
+    write.write_all(&bytes)
+}
+
+fn read_u64(data: &[u8]) -> u64 {
+    (0..8).fold(0u64, |acc, i| acc | ((data[i] as u64) << (8 * i)))
+}
+
+/// A compact bitset over a segment's document ids, persisted as the
+/// segment's `.del` file.
+///
+/// Bit `doc` is `1` while `doc` is alive, and `0` once it has been
+/// deleted. `SegmentReader` uses it to skip tombstoned docs during a
+/// search.
+pub struct AliveBitSet {
+    data: Vec<u64>,
+    len: u32,
+    num_deleted: u32,
+}
+
+impl AliveBitSet {
+    /// Creates a bitset with every doc in `0..max_doc` marked alive.
+    pub fn new(max_doc: u32) -> AliveBitSet {
+        let num_words = (max_doc as usize + 63) / 64;
+        AliveBitSet {
+            data: vec![!0u64; num_words],
+            len: max_doc,
+            num_deleted: 0,
+        }
+    }
+
+    /// Marks `doc` as deleted.
+    ///
+    /// Deleting an already-deleted doc is a no-op.
+    pub fn delete(&mut self, doc: u32) {
+        if self.is_alive(doc) {
+            let word = (doc / 64) as usize;
+            let bit = doc % 64;
+            self.data[word] &= !(1u64 << bit);
+            self.num_deleted += 1;
+        }
+    }
+
+    /// Returns whether `doc` has been deleted.
+    pub fn is_deleted(&self, doc: u32) -> bool {
+        !self.is_alive(doc)
+    }
+
+    /// Returns whether `doc` is still alive.
+    pub fn is_alive(&self, doc: u32) -> bool {
+        let word = (doc / 64) as usize;
+        let bit = doc % 64;
+        (self.data[word] >> bit) & 1 == 1
+    }
+
+    /// Returns the total number of documents tracked by this bitset,
+    /// alive and deleted.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Returns the number of documents that have been deleted.
+    pub fn num_deleted(&self) -> u32 {
+        self.num_deleted
+    }
+
+    /// Serializes the bitset to its on-disk `.del` file representation.
+    pub fn write(&self, write: &mut Write) -> io::Result<()> {
+        try!(write_u32(write, self.len));
+        try!(write_u32(write, self.num_deleted));
+        for &word in &self.data {
+            try!(write_u64(write, word));
+        }
+        Ok(())
+    }
+
+    /// Reads back a bitset previously written by `write`.
+    ///
+    /// Validates the declared length against the actual buffer size
+    /// before indexing into it, so a `.del` file truncated by a crash
+    /// mid-write surfaces as an `io::Error` instead of panicking.
+    pub fn open(source: &ReadOnlySource) -> io::Result<AliveBitSet> {
+        let data = source.as_slice();
+        if data.len() < 8 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "AliveBitSet file is too small to contain its header",
+            ));
+        }
+        let len = read_u32(&data[0..4]);
+        let num_deleted = read_u32(&data[4..8]);
+        let num_words = (len as usize + 63) / 64;
+        if data.len() < 8 + num_words * 8 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "AliveBitSet file is smaller than its header declares",
+            ));
+        }
+        let mut words = Vec::with_capacity(num_words);
+        for i in 0..num_words {
+            let start = 8 + i * 8;
+            words.push(read_u64(&data[start..start + 8]));
+        }
+        Ok(AliveBitSet {
+            data: words,
+            len: len,
+            num_deleted: num_deleted,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_alive_bitset_delete() {
+        let mut bitset = AliveBitSet::new(100);
+        assert!(bitset.is_alive(42));
+        bitset.delete(42);
+        assert!(bitset.is_deleted(42));
+        assert_eq!(bitset.num_deleted(), 1);
+        bitset.delete(42);
+        assert_eq!(bitset.num_deleted(), 1);
+    }
+
+    #[test]
+    fn test_alive_bitset_roundtrip() {
+        let mut bitset = AliveBitSet::new(200);
+        bitset.delete(0);
+        bitset.delete(63);
+        bitset.delete(64);
+        bitset.delete(199);
+        let mut buffer = Vec::new();
+        bitset.write(&mut buffer).unwrap();
+        let source = ReadOnlySource::from(buffer);
+        let reloaded = AliveBitSet::open(&source).unwrap();
+        for doc in 0..200 {
+            assert_eq!(bitset.is_deleted(doc), reloaded.is_deleted(doc));
+        }
+        assert_eq!(reloaded.num_deleted(), 4);
+    }
+}
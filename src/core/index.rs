@@ -7,7 +7,7 @@ use std::sync::{Arc, RwLock};
 use std::fmt;
 use rustc_serialize::json;
 use core::SegmentId;
-use directory::{Directory, MmapDirectory, RAMDirectory};
+use directory::{Directory, MmapDirectory, RAMDirectory, GarbageCollectionResult, SegmentComponent};
 use indexer::IndexWriter;
 use core::searcher::Searcher;
 use std::convert::From;
@@ -17,23 +17,37 @@ use super::segment::Segment;
 use core::SegmentReader;
 use super::pool::Pool;
 use super::pool::LeasedItem;
+use core::merge_policy::{DefaultMergePolicy, SegmentMeta};
+use indexer::segment_updater::SegmentUpdater;
+use indexer::delete_queue::{DeleteQueue, DeleteCursor};
+use core::alive_bitset::AliveBitSet;
+use core::stamp::{Opstamp, Stamper};
 
 
-const NUM_SEARCHERS: usize = 12; 
+const NUM_SEARCHERS: usize = 12;
+
+const ALL_SEGMENT_COMPONENTS: [SegmentComponent; 6] = [
+    SegmentComponent::POSTINGS,
+    SegmentComponent::POSITIONS,
+    SegmentComponent::TERMS,
+    SegmentComponent::STORE,
+    SegmentComponent::FASTFIELDS,
+    SegmentComponent::FIELDNORMS,
+];
 
 /// MetaInformation about the `Index`.
-/// 
+///
 /// This object is serialized on disk in the `meta.json` file.
-/// It keeps information about 
+/// It keeps information about
 /// * the searchable segments,
-/// * the index docstamp
+/// * the last committed opstamp,
 /// * the schema
 ///
 #[derive(Clone,Debug,RustcDecodable,RustcEncodable)]
 pub struct IndexMeta {
-    segments: Vec<SegmentId>,
+    segments: Vec<SegmentMeta>,
     schema: Schema,
-    docstamp: u64,
+    opstamp: Opstamp,
 }
 
 impl IndexMeta {
@@ -41,7 +55,7 @@ impl IndexMeta {
         IndexMeta {
             segments: Vec::new(),
             schema: schema,
-            docstamp: 0u64,
+            opstamp: 0u64,
         }
     }
 }
@@ -51,14 +65,23 @@ lazy_static! {
 }
 
 
+/// Number of times `load_metas` will re-read `meta.json` if it fails
+/// to decode, to tolerate a read racing a concurrent `atomic_write`
+/// (most visible on NFS mounts, where writes are not guaranteed to be
+/// atomic from a reader's point of view).
+const LOAD_METAS_NUM_RETRIES: usize = 5;
+
 fn load_metas(directory: &Directory) -> Result<IndexMeta> {
-    let meta_file = try!(directory.open_read(&META_FILEPATH));
-    let meta_content = String::from_utf8_lossy(meta_file.as_slice());
-    let loaded_meta = try!(
-        json::decode(&meta_content)
-            .map_err(|e| Error::CorruptedFile(META_FILEPATH.clone(), Box::new(e)))
-    );
-    Ok(loaded_meta)
+    let mut last_err = None;
+    for _ in 0..LOAD_METAS_NUM_RETRIES {
+        let meta_file = try!(directory.open_read(&META_FILEPATH));
+        let meta_content = String::from_utf8_lossy(meta_file.as_slice());
+        match json::decode(&meta_content) {
+            Ok(loaded_meta) => return Ok(loaded_meta),
+            Err(e) => last_err = Some(Error::CorruptedFile(META_FILEPATH.clone(), Box::new(e))),
+        }
+    }
+    Err(last_err.expect("LOAD_METAS_NUM_RETRIES must be greater than 0"))
 }
 
 /// Tantivy's Search Index
@@ -67,6 +90,9 @@ pub struct Index {
     directory: Box<Directory>,
     schema: Schema,
     searcher_pool: Arc<Pool<Searcher>>,
+    segment_updater: Arc<SegmentUpdater>,
+    delete_queue: DeleteQueue,
+    stamper: Stamper,
 }
 
 impl Index {
@@ -103,11 +129,15 @@ impl Index {
     /// Creates a new index given a directory and an `IndexMeta`.
     fn create_from_metas(directory: Box<Directory>, metas: IndexMeta) -> Result<Index> {
         let schema = metas.schema.clone();
+        let stamper = Stamper::new(metas.opstamp);
         let index = Index {
             directory: directory,
             metas: Arc::new(RwLock::new(metas)),
             schema: schema,
             searcher_pool: Arc::new(Pool::new()),
+            segment_updater: Arc::new(SegmentUpdater::new(Box::new(DefaultMergePolicy::new()))),
+            delete_queue: DeleteQueue::new(),
+            stamper: stamper,
         };
         try!(index.load_searchers());
         Ok(index)
@@ -127,16 +157,22 @@ impl Index {
         Index::create_from_metas(directory.box_clone(), metas)
     }
     
-    /// Returns the index docstamp.
-    ///
-    /// The docstamp is the number of documents that have been added
-    /// from the beginning of time, and until the moment of the last commit.
-    pub fn docstamp(&self,) -> Result<u64> {
+    /// Returns the opstamp of the last commit.
+    pub fn docstamp(&self,) -> Result<Opstamp> {
         self.metas
             .read()
-            .map(|metas| metas.docstamp)
+            .map(|metas| metas.opstamp)
             .map_err(From::from)
     }
+
+    /// Returns the next opstamp, advancing the index's `Stamper`.
+    ///
+    /// Every add or delete operation should call this exactly once, so
+    /// that operations end up with a total order across all of a
+    /// writer's threads.
+    pub fn new_opstamp(&self,) -> Opstamp {
+        self.stamper.stamp()
+    }
     
     /// Creates a multithreaded writer.
     /// Each writer produces an independent segment.
@@ -167,39 +203,143 @@ impl Index {
         self.schema.clone()
     }
 
-    /// Marks the segment as published.
+    /// Marks the segments as published.
     // TODO find a rusty way to hide that, while keeping
     // it visible for `IndexWriter`s.
     pub fn publish_segments(&mut self,
-            segment_ids: &[SegmentId],
-            docstamp: u64) -> Result<()> {
+            segment_metas: &[SegmentMeta],
+            at_opstamp: Opstamp) -> Result<()> {
         {
             let mut meta_write = try!(self.metas.write());
-            meta_write.segments.extend_from_slice(segment_ids);
-            meta_write.docstamp = docstamp;
+            meta_write.segments.extend_from_slice(segment_metas);
+            meta_write.opstamp = at_opstamp;
         }
         try!(self.save_metas());
         try!(self.load_searchers());
+        self.consider_merges();
         Ok(())
     }
 
-    /// Exchange a set of `SegmentId`s for the `SegmentId` of a merged segment.   
+    /// Exchange a set of `SegmentId`s for the `SegmentId` of a merged segment.
     pub fn publish_merge_segment(&mut self, segment_merged_ids: HashSet<SegmentId>, merged_segment_id: SegmentId) -> Result<()> {
         {
             let mut meta_write = try!(self.metas.write());
-            let mut new_segment_ids: Vec<SegmentId> = meta_write
+            let (merged_num_docs, merged_opstamp_range) = {
+                let merged_segments: Vec<&SegmentMeta> = meta_write
+                    .segments
+                    .iter()
+                    .filter(|segment_meta| segment_merged_ids.contains(&segment_meta.id()))
+                    .collect();
+                let merged_num_docs: u32 = merged_segments
+                    .iter()
+                    .map(|segment_meta| segment_meta.num_docs())
+                    .sum();
+                let merged_opstamp_range = (
+                    merged_segments.iter().map(|segment_meta| segment_meta.opstamp_range().0).min().unwrap_or(0),
+                    merged_segments.iter().map(|segment_meta| segment_meta.opstamp_range().1).max().unwrap_or(0),
+                );
+                (merged_num_docs, merged_opstamp_range)
+            };
+            let mut new_segments: Vec<SegmentMeta> = meta_write
                 .segments
                 .iter()
-                .filter(|&segment_id| !segment_merged_ids.contains(segment_id))
+                .filter(|segment_meta| !segment_merged_ids.contains(&segment_meta.id()))
                 .cloned()
                 .collect();
-            new_segment_ids.push(merged_segment_id);
-            meta_write.segments = new_segment_ids;
+            new_segments.push(SegmentMeta::new(merged_segment_id, merged_num_docs, merged_opstamp_range));
+            meta_write.segments = new_segments;
         }
         try!(self.save_metas());
         try!(self.load_searchers());
         Ok(())
     }
+
+    /// Returns the current `SegmentMeta` of every searchable segment.
+    pub fn segment_metas(&self) -> Result<Vec<SegmentMeta>> {
+        self.metas
+            .read()
+            .map(|meta_read| meta_read.segments.clone())
+            .map_err(From::from)
+    }
+
+    /// Replaces the current segments wholesale and commits at
+    /// `at_opstamp`.
+    ///
+    /// Used by `IndexWriter::commit` once it has applied every
+    /// pending delete to each segment via `advance_deletes`.
+    pub fn replace_segment_metas(&mut self, segment_metas: Vec<SegmentMeta>, at_opstamp: Opstamp) -> Result<()> {
+        {
+            let mut meta_write = try!(self.metas.write());
+            meta_write.segments = segment_metas;
+            meta_write.opstamp = at_opstamp;
+        }
+        try!(self.save_metas());
+        try!(self.load_searchers());
+        Ok(())
+    }
+
+    /// Returns the index's shared `DeleteQueue`.
+    ///
+    /// `IndexWriter::delete_term` pushes onto it; each segment reads
+    /// its own `DeleteCursor` (see `DeleteQueue::cursor`) to know which
+    /// of these operations it still needs to apply.
+    pub fn delete_queue(&self) -> DeleteQueue {
+        self.delete_queue.clone()
+    }
+
+    /// Applies the delete operations a segment hasn't seen yet and
+    /// persists the resulting `AliveBitSet` as that segment's `.del`
+    /// file.
+    ///
+    /// Returns the `SegmentMeta` updated with the new deleted-doc
+    /// count and delete opstamp, ready to be written back into
+    /// `IndexMeta` by the caller.
+    ///
+    /// Note: `SegmentReader::doc_ids_for_term`, which this relies on to
+    /// turn a pending delete-by-term into concrete doc ids, is currently
+    /// a no-op in this tree (no term dictionary / postings reader
+    /// exists yet), so this method faithfully updates opstamps and
+    /// writes a `.del` file but never actually flips a document off.
+    pub fn advance_deletes(
+        &self,
+        segment_meta: &SegmentMeta,
+        delete_cursor: &mut DeleteCursor,
+        at_opstamp: Opstamp,
+    ) -> Result<SegmentMeta> {
+        let pending_deletes = delete_cursor.drain();
+        if pending_deletes.is_empty() {
+            return Ok(segment_meta.clone());
+        }
+        let segment = self.segment(segment_meta.id());
+        let segment_reader = try!(SegmentReader::open(segment));
+        let del_path = self.delete_file_path(segment_meta.id());
+        let mut alive_bitset = match self.directory.open_read(&del_path) {
+            Ok(source) => try!(AliveBitSet::open(&source).map_err(Error::from)),
+            Err(_) => AliveBitSet::new(segment_meta.num_docs()),
+        };
+        for delete_op in pending_deletes {
+            for doc in segment_reader.doc_ids_for_term(&delete_op.term) {
+                alive_bitset.delete(doc);
+            }
+        }
+        let mut buffer = Vec::new();
+        try!(alive_bitset.write(&mut buffer));
+        try!(self.directory.box_clone().atomic_write(&del_path, &buffer[..]));
+        Ok(segment_meta.with_delete_meta(alive_bitset.num_deleted(), at_opstamp))
+    }
+
+    fn delete_file_path(&self, segment_id: SegmentId) -> PathBuf {
+        PathBuf::from(format!("{}.del", segment_id.uuid_string()))
+    }
+
+    /// Asks the `SegmentUpdater`'s `MergePolicy` for merge candidates
+    /// given the current segments, and schedules them in the
+    /// background.
+    fn consider_merges(&self) {
+        if let Ok(meta_read) = self.metas.read() {
+            self.segment_updater.consider_merges(self, &meta_read.segments[..]);
+        }
+    }
     
     /// Returns the list of segments that are searchable
     pub fn segments(&self,) -> Result<Vec<Segment>> {
@@ -216,7 +356,7 @@ impl Index {
     /// Return a segment object given a `segment_id`
     ///
     /// The segment may or may not exist.
-    fn segment(&self, segment_id: SegmentId) -> Segment {
+    pub(crate) fn segment(&self, segment_id: SegmentId) -> Segment {
         Segment::new(self.clone(), segment_id)
     }
     
@@ -239,10 +379,10 @@ impl Index {
             meta_read
             .segments
             .iter()
-            .cloned()
+            .map(|segment_meta| segment_meta.id())
             .collect()
         })
-            
+
     }
     
     /// Creates a new segment.
@@ -263,9 +403,43 @@ impl Index {
             let metas_lock = try!(self.metas.read());
             try!(write!(&mut w, "{}\n", json::as_pretty_json(&*metas_lock)));
         };
-        self.directory
-            .atomic_write(&META_FILEPATH, &w[..])
-            .map_err(From::from)
+        try!(
+            self.directory
+                .atomic_write(&META_FILEPATH, &w[..])
+                .map_err(From::from)
+        );
+        // A failed garbage collection pass should not fail the commit
+        // that triggered it; the orphaned files will simply be swept
+        // up on the next one.
+        let _ = self.garbage_collect();
+        Ok(())
+    }
+
+    /// Deletes the files of segments that are no longer referenced by
+    /// the current `IndexMeta` (typically merged-away segments, or
+    /// partial files left behind by a failed write).
+    ///
+    /// This is called automatically after every successful
+    /// `save_metas`.
+    pub fn garbage_collect(&self,) -> Result<GarbageCollectionResult> {
+        let live_files = try!(self.list_live_files());
+        Ok(self.directory.garbage_collect(live_files))
+    }
+
+    fn list_live_files(&self,) -> Result<HashSet<PathBuf>> {
+        let meta_read = try!(self.metas.read());
+        let mut live_files = HashSet::new();
+        live_files.insert(META_FILEPATH.clone());
+        for segment_meta in &meta_read.segments {
+            let segment = self.segment(segment_meta.id());
+            for &component in &ALL_SEGMENT_COMPONENTS {
+                live_files.insert(segment.relative_path(component));
+            }
+            if segment_meta.delete_opstamp().is_some() {
+                live_files.insert(self.delete_file_path(segment_meta.id()));
+            }
+        }
+        Ok(live_files)
     }
     
     /// Creates a new generation of searchers after 
@@ -320,6 +494,9 @@ impl Clone for Index {
             directory: self.directory.box_clone(),
             schema: self.schema.clone(),
             searcher_pool: self.searcher_pool.clone(),
+            segment_updater: self.segment_updater.clone(),
+            delete_queue: self.delete_queue.clone(),
+            stamper: self.stamper.clone(),
         }
     }
 }
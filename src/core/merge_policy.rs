@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use core::SegmentId;
+use core::stamp::Opstamp;
+
+/// Lightweight, serializable description of a segment as tracked in
+/// `IndexMeta`. This is the information a `MergePolicy` gets to look
+/// at when deciding what to merge; it deliberately does not require
+/// opening the segment itself.
+#[derive(Clone, Debug, RustcDecodable, RustcEncodable)]
+pub struct SegmentMeta {
+    segment_id: SegmentId,
+    num_docs: u32,
+    num_deleted_docs: u32,
+    delete_opstamp: Option<Opstamp>,
+    opstamp_range: (Opstamp, Opstamp),
+}
+
+impl SegmentMeta {
+    /// Creates a new `SegmentMeta`, with no deletes applied yet.
+    ///
+    /// `opstamp_range` is the inclusive range of opstamps of the
+    /// operations that went into this segment. It is persisted in
+    /// `IndexMeta` with the rest of the `SegmentMeta`; `load_searchers`
+    /// does not consult it.
+    pub fn new(segment_id: SegmentId, num_docs: u32, opstamp_range: (Opstamp, Opstamp)) -> SegmentMeta {
+        SegmentMeta {
+            segment_id: segment_id,
+            num_docs: num_docs,
+            num_deleted_docs: 0,
+            delete_opstamp: None,
+            opstamp_range: opstamp_range,
+        }
+    }
+
+    /// Returns the id of the segment this `SegmentMeta` describes.
+    pub fn id(&self) -> SegmentId {
+        self.segment_id
+    }
+
+    /// Returns the number of documents contained in the segment,
+    /// including documents that have since been deleted.
+    pub fn num_docs(&self) -> u32 {
+        self.num_docs
+    }
+
+    /// Returns the number of documents in the segment that have been
+    /// deleted, as of `delete_opstamp`.
+    pub fn num_deleted_docs(&self) -> u32 {
+        self.num_deleted_docs
+    }
+
+    /// Returns the opstamp of the last delete operation applied to
+    /// this segment's `.del` file, if any.
+    pub fn delete_opstamp(&self) -> Option<Opstamp> {
+        self.delete_opstamp
+    }
+
+    /// Returns the inclusive range of opstamps covered by this
+    /// segment's documents.
+    pub fn opstamp_range(&self) -> (Opstamp, Opstamp) {
+        self.opstamp_range
+    }
+
+    /// Returns a copy of this `SegmentMeta` with its deletion
+    /// metadata updated to reflect a freshly written `.del` file.
+    pub fn with_delete_meta(&self, num_deleted_docs: u32, delete_opstamp: Opstamp) -> SegmentMeta {
+        SegmentMeta {
+            segment_id: self.segment_id,
+            num_docs: self.num_docs,
+            num_deleted_docs: num_deleted_docs,
+            delete_opstamp: Some(delete_opstamp),
+            opstamp_range: self.opstamp_range,
+        }
+    }
+}
+
+/// A set of segments that a `MergePolicy` proposes to merge together
+/// into a single, new segment.
+pub type MergeCandidate = Vec<SegmentId>;
+
+/// Decides which segments should be merged together.
+///
+/// `SegmentUpdater` consults the policy after every `publish_segments`
+/// and schedules each returned `MergeCandidate` on its merge thread
+/// pool.
+pub trait MergePolicy: Send + Sync + 'static {
+    /// Given the current set of searchable segments, returns the
+    /// groups of segments that should be merged.
+    ///
+    /// A segment may appear in at most one candidate.
+    fn compute_merge_candidates(&self, segments: &[SegmentMeta]) -> Vec<MergeCandidate>;
+}
+
+/// Tiered merge policy.
+///
+/// Segments are bucketed by `log2(num_docs)`. Whenever a bucket
+/// accumulates more than `min_merge_size` segments, those segments are
+/// proposed as a single merge candidate.
+pub struct DefaultMergePolicy {
+    min_merge_size: usize,
+}
+
+impl DefaultMergePolicy {
+    /// Creates a `DefaultMergePolicy` that merges a size tier as soon
+    /// as it holds more than 8 segments.
+    pub fn new() -> DefaultMergePolicy {
+        DefaultMergePolicy { min_merge_size: 8 }
+    }
+
+    fn size_tier(num_docs: u32) -> u32 {
+        if num_docs == 0 {
+            0
+        } else {
+            32 - num_docs.leading_zeros()
+        }
+    }
+}
+
+impl Default for DefaultMergePolicy {
+    fn default() -> DefaultMergePolicy {
+        DefaultMergePolicy::new()
+    }
+}
+
+impl MergePolicy for DefaultMergePolicy {
+    fn compute_merge_candidates(&self, segments: &[SegmentMeta]) -> Vec<MergeCandidate> {
+        let mut tiers: HashMap<u32, Vec<SegmentId>> = HashMap::new();
+        for segment_meta in segments {
+            tiers
+                .entry(DefaultMergePolicy::size_tier(segment_meta.num_docs()))
+                .or_insert_with(Vec::new)
+                .push(segment_meta.id());
+        }
+        tiers
+            .into_iter()
+            .filter(|&(_, ref segment_ids)| segment_ids.len() > self.min_merge_size)
+            .map(|(_, segment_ids)| segment_ids)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use core::SegmentId;
+
+    #[test]
+    fn test_default_merge_policy_groups_by_size_tier() {
+        let policy = DefaultMergePolicy::new();
+        let mut segments = Vec::new();
+        for _ in 0..9 {
+            segments.push(SegmentMeta::new(SegmentId::generate_random(), 100, (0, 99)));
+        }
+        let candidates = policy.compute_merge_candidates(&segments[..]);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].len(), 9);
+    }
+
+    #[test]
+    fn test_default_merge_policy_no_candidate_below_threshold() {
+        let policy = DefaultMergePolicy::new();
+        let segments = vec![SegmentMeta::new(SegmentId::generate_random(), 100, (0, 99))];
+        let candidates = policy.compute_merge_candidates(&segments[..]);
+        assert!(candidates.is_empty());
+    }
+}
@@ -0,0 +1,100 @@
+use std::path::PathBuf;
+use std::fmt;
+
+use Result;
+use Error;
+use core::{Index, SegmentId};
+use core::alive_bitset::AliveBitSet;
+use directory::{CompoundDirectory, CompoundFile, CompoundFileWriter, SegmentComponent};
+
+/// A single segment of an `Index`.
+///
+/// Every component stream of a segment (postings, terms, fast fields,
+/// store, ...) is packed into one physical, compound file named after
+/// the segment's id, via `CompoundFileWriter`/`CompoundFile`. This
+/// keeps the number of open files at `O(segments)` rather than
+/// `O(segments * components)`.
+#[derive(Clone)]
+pub struct Segment {
+    index: Index,
+    segment_id: SegmentId,
+}
+
+impl Segment {
+    /// Creates a `Segment` handle for `segment_id` within `index`.
+    ///
+    /// This does not touch the directory: the segment's files may or
+    /// may not exist yet.
+    pub fn new(index: Index, segment_id: SegmentId) -> Segment {
+        Segment {
+            index: index,
+            segment_id: segment_id,
+        }
+    }
+
+    /// Returns this segment's id.
+    pub fn id(&self) -> SegmentId {
+        self.segment_id
+    }
+
+    /// Returns the path of this segment's compound file.
+    ///
+    /// Every `SegmentComponent` lives in this same physical file; the
+    /// `component` argument only exists so that callers thinking in
+    /// terms of "one file per component" (e.g. garbage collection's
+    /// live-file set) keep working unchanged.
+    pub fn relative_path(&self, _component: SegmentComponent) -> PathBuf {
+        PathBuf::from(format!("{}.seg", self.segment_id.uuid_string()))
+    }
+
+    fn delete_file_path(&self) -> PathBuf {
+        PathBuf::from(format!("{}.del", self.segment_id.uuid_string()))
+    }
+
+    /// Starts writing this segment.
+    ///
+    /// The caller fills in one component at a time via
+    /// `CompoundFileWriter::component_write`, then hands the writer
+    /// back to `finalize`.
+    pub fn open_write(&self) -> CompoundFileWriter {
+        CompoundFileWriter::new()
+    }
+
+    /// Serializes `writer`'s components into this segment's compound
+    /// file, making it visible to future `open_read` calls.
+    pub fn finalize(&self, writer: CompoundFileWriter) -> Result<()> {
+        let mut compound_directory = CompoundDirectory::wrap(self.index.directory().box_clone());
+        compound_directory
+            .write_compound(&self.relative_path(SegmentComponent::STORE), writer)
+            .map_err(Error::from)
+    }
+
+    /// Opens this segment's compound file for reading.
+    pub fn open_read(&self) -> Result<CompoundFile> {
+        let compound_directory = CompoundDirectory::wrap(self.index.directory().box_clone());
+        compound_directory
+            .read_compound(&self.relative_path(SegmentComponent::STORE))
+            .map_err(Error::from)
+    }
+
+    /// Opens this segment's `.del` file, if deletes have ever been
+    /// applied to it.
+    ///
+    /// Returns `Ok(None)` when no `.del` file exists yet, which is the
+    /// normal state for a segment with no deletes. A `.del` file that
+    /// does exist but fails to parse (e.g. truncated by a crash
+    /// mid-write) is a distinct, real error and is propagated as such
+    /// rather than silently treated as "no deletes".
+    pub fn open_alive_bitset(&self) -> Result<Option<AliveBitSet>> {
+        match self.index.directory().open_read(&self.delete_file_path()) {
+            Ok(source) => AliveBitSet::open(&source).map(Some).map_err(Error::from),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+impl fmt::Debug for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Segment({:?})", self.segment_id)
+    }
+}
@@ -0,0 +1,73 @@
+use Result;
+use core::Segment;
+use core::alive_bitset::AliveBitSet;
+use directory::CompoundFile;
+use schema::Term;
+
+/// Read-only view over a single segment, built by opening its
+/// compound file and, if present, its `.del` file.
+///
+/// Every generation of `Searcher` published by `Index::load_searchers`
+/// holds one `SegmentReader` per searchable segment.
+pub struct SegmentReader {
+    segment: Segment,
+    compound_file: CompoundFile,
+    alive_bitset: Option<AliveBitSet>,
+}
+
+impl SegmentReader {
+    /// Opens `segment`'s compound file, loading its `AliveBitSet` if
+    /// the segment has a `.del` file.
+    pub fn open(segment: Segment) -> Result<SegmentReader> {
+        let compound_file = try!(segment.open_read());
+        let alive_bitset = try!(segment.open_alive_bitset());
+        Ok(SegmentReader {
+            segment: segment,
+            compound_file: compound_file,
+            alive_bitset: alive_bitset,
+        })
+    }
+
+    /// Returns the segment this reader is reading.
+    pub fn segment(&self) -> &Segment {
+        &self.segment
+    }
+
+    /// Returns this segment's compound file, for readers of individual
+    /// components (postings, terms, fast fields, store, ...).
+    pub fn compound_file(&self) -> &CompoundFile {
+        &self.compound_file
+    }
+
+    /// Returns whether `doc` has been deleted.
+    ///
+    /// A segment with no `.del` file has no deleted docs at all.
+    pub fn is_deleted(&self, doc: u32) -> bool {
+        self.alive_bitset
+            .as_ref()
+            .map_or(false, |alive_bitset| alive_bitset.is_deleted(doc))
+    }
+
+    /// Returns this segment's `AliveBitSet`, if the segment has a `.del`
+    /// file. `Index::advance_deletes` writes one whenever delete
+    /// operations were applied to the segment, even if none of them
+    /// matched a document.
+    pub fn alive_docs(&self) -> Option<&AliveBitSet> {
+        self.alive_bitset.as_ref()
+    }
+
+    /// Returns the doc ids indexed under `term`.
+    ///
+    /// No-op today: this tree has no real term dictionary / postings
+    /// reader to resolve a `Term` against, so this always returns an
+    /// empty `Vec` regardless of what was indexed. `Index::advance_deletes`
+    /// calls this to pick which docs a pending delete-by-term should mark
+    /// deleted, which means deletes currently flow end-to-end (opstamp
+    /// bookkeeping, `.del` file writes, `num_deleted_docs`) without ever
+    /// actually flipping a single doc off in an `AliveBitSet`. Wire a
+    /// real term dictionary in here before relying on deletes in
+    /// production.
+    pub fn doc_ids_for_term(&self, _term: &Term) -> Vec<u32> {
+        Vec::new()
+    }
+}
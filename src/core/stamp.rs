@@ -0,0 +1,71 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Monotonically increasing identifier assigned to every add or delete
+/// operation.
+///
+/// Opstamps let a segment know exactly which of the operations
+/// recorded in the `DeleteQueue` it still needs to apply, and let
+/// `IndexMeta` order commits without relying on wall-clock time.
+pub type Opstamp = u64;
+
+/// Hands out a strictly increasing sequence of `Opstamp`s.
+///
+/// `Index` owns a single `Stamper`; every add or delete goes through
+/// it first so that, across all of the writer's threads, operations
+/// end up with a total order instead of just a per-thread one.
+///
+/// Backed by `AtomicU64`, not `AtomicUsize`: `Opstamp` is a `u64`
+/// regardless of target pointer width, and `usize` would silently
+/// truncate it on 32-bit targets.
+#[derive(Clone)]
+pub struct Stamper {
+    inner: Arc<AtomicU64>,
+}
+
+impl Stamper {
+    /// Creates a `Stamper` whose next call to `stamp()` returns
+    /// `first_opstamp`.
+    pub fn new(first_opstamp: Opstamp) -> Stamper {
+        Stamper {
+            inner: Arc::new(AtomicU64::new(first_opstamp)),
+        }
+    }
+
+    /// Returns the next opstamp and advances the counter.
+    pub fn stamp(&self) -> Opstamp {
+        self.inner.fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// Rewinds the counter back to `opstamp`.
+    ///
+    /// Used to discard opstamps that were handed out to operations
+    /// that never actually got committed, so a future rollback can
+    /// truncate them without leaving a permanent gap in the sequence.
+    pub fn revert_to(&self, opstamp: Opstamp) {
+        self.inner.store(opstamp, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_stamper_increments() {
+        let stamper = Stamper::new(0);
+        assert_eq!(stamper.stamp(), 0);
+        assert_eq!(stamper.stamp(), 1);
+        assert_eq!(stamper.stamp(), 2);
+    }
+
+    #[test]
+    fn test_stamper_revert() {
+        let stamper = Stamper::new(0);
+        stamper.stamp();
+        stamper.stamp();
+        stamper.revert_to(1);
+        assert_eq!(stamper.stamp(), 1);
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+use rustc_serialize::json;
+
+use directory::{Directory, ReadOnlySource};
+use directory::error::{FileError, OpenWriteError};
+
+/// Identifies one of the logical streams (postings, terms, fast fields, store, ...)
+/// that get packed together into a single compound segment file.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, RustcEncodable, RustcDecodable)]
+pub enum SegmentComponent {
+    POSTINGS,
+    POSITIONS,
+    TERMS,
+    STORE,
+    FASTFIELDS,
+    FIELDNORMS,
+}
+
+/// One entry of the compound file footer: where a given component
+/// starts and how many bytes it occupies within the compound file.
+#[derive(RustcEncodable, RustcDecodable)]
+struct FooterEntry {
+    component: SegmentComponent,
+    start: u64,
+    len: u64,
+}
+
+/// Accumulates the different component streams of a segment and
+/// serializes them into a single physical file.
+///
+/// Components are written one after the other, and the resulting
+/// offset table (the "footer") is appended at the end, followed by
+/// its own byte length encoded as a little-endian `u64`. This lets
+/// a reader seek to `EOF - 8`, recover the footer length, and read
+/// the footer back without knowing it upfront.
+pub struct CompoundFileWriter {
+    buffers: Vec<(SegmentComponent, Vec<u8>)>,
+}
+
+impl CompoundFileWriter {
+    /// Creates a new, empty compound file writer.
+    pub fn new() -> CompoundFileWriter {
+        CompoundFileWriter {
+            buffers: Vec::new(),
+        }
+    }
+
+    /// Returns a `Write` handle for a given component.
+    ///
+    /// Each component may only be written once.
+    pub fn component_write(&mut self, component: SegmentComponent) -> &mut Vec<u8> {
+        self.buffers.push((component, Vec::new()));
+        &mut self.buffers.last_mut().unwrap().1
+    }
+
+    /// Serializes all of the components written so far into `write`,
+    /// one after the other, followed by the footer and its length.
+    pub fn finalize(self, write: &mut Write) -> io::Result<()> {
+        let mut footer = Vec::with_capacity(self.buffers.len());
+        let mut offset = 0u64;
+        for (component, buffer) in self.buffers {
+            try!(write.write_all(&buffer[..]));
+            footer.push(FooterEntry {
+                component: component,
+                start: offset,
+                len: buffer.len() as u64,
+            });
+            offset += buffer.len() as u64;
+        }
+        let footer_json = json::encode(&footer).expect("Failed to encode compound file footer");
+        let footer_bytes = footer_json.into_bytes();
+        try!(write.write_all(&footer_bytes[..]));
+        let footer_len = footer_bytes.len() as u64;
+        let footer_len_bytes: [u8; 8] = [
+            (footer_len & 0xff) as u8,
+            ((footer_len >> 8) & 0xff) as u8,
+            ((footer_len >> 16) & 0xff) as u8,
+            ((footer_len >> 24) & 0xff) as u8,
+            ((footer_len >> 32) & 0xff) as u8,
+            ((footer_len >> 40) & 0xff) as u8,
+            ((footer_len >> 48) & 0xff) as u8,
+            ((footer_len >> 56) & 0xff) as u8,
+        ];
+        write.write_all(&footer_len_bytes)
+    }
+}
+
+/// A compound segment file, as produced by `CompoundFileWriter`.
+///
+/// Internally it is backed by a single `ReadOnlySource` (typically an
+/// mmap of the whole file) and hands out sub-slices of it for each
+/// component, so opening a segment only ever mmaps one file regardless
+/// of how many components it is made of.
+pub struct CompoundFile {
+    source: ReadOnlySource,
+    offsets: HashMap<SegmentComponent, (usize, usize)>,
+}
+
+impl CompoundFile {
+    /// Reads the footer at the end of `source` and builds the
+    /// component -> byte range map used to serve `open_read`.
+    pub fn open(source: ReadOnlySource) -> Result<CompoundFile, FileError> {
+        let data = source.as_slice();
+        if data.len() < 8 {
+            return Err(FileError::IOError(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Compound file is too small to contain a footer",
+            )));
+        }
+        let footer_len_bytes = &data[data.len() - 8..];
+        let footer_len = (0..8).fold(0u64, |acc, i| {
+            acc | ((footer_len_bytes[i] as u64) << (8 * i))
+        }) as usize;
+        if data.len() < 8 + footer_len {
+            return Err(FileError::IOError(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Compound file footer length is inconsistent with file size",
+            )));
+        }
+        let footer_start = data.len() - 8 - footer_len;
+        let footer_json = String::from_utf8_lossy(&data[footer_start..footer_start + footer_len]);
+        let footer: Vec<FooterEntry> = try!(json::decode(&footer_json).map_err(|e| {
+            FileError::IOError(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to decode compound file footer: {}", e),
+            ))
+        }));
+        let offsets = footer
+            .into_iter()
+            .map(|entry| (entry.component, (entry.start as usize, entry.len as usize)))
+            .collect();
+        Ok(CompoundFile {
+            source: source,
+            offsets: offsets,
+        })
+    }
+
+    /// Returns the sub-slice of the compound file's single mmap that
+    /// holds the requested component, if present.
+    pub fn open_read(&self, component: SegmentComponent) -> Option<ReadOnlySource> {
+        self.offsets
+            .get(&component)
+            .map(|&(start, len)| self.source.slice(start, start + len))
+    }
+}
+
+/// A `Directory` wrapper that lets callers pack a segment's many
+/// component streams into a single physical file, cutting the number
+/// of open file descriptors / mmaps from `O(segments * components)`
+/// down to `O(segments)`.
+///
+/// All non-segment files (`meta.json`, the lockfile, ...) are simply
+/// delegated to the wrapped directory unchanged.
+pub struct CompoundDirectory {
+    inner: Box<Directory>,
+}
+
+impl CompoundDirectory {
+    /// Wraps an existing `Directory` with compound-file support.
+    pub fn wrap(inner: Box<Directory>) -> CompoundDirectory {
+        CompoundDirectory { inner: inner }
+    }
+
+    /// Writes a fully built `CompoundFileWriter` to `path` as a single file.
+    pub fn write_compound(
+        &mut self,
+        path: &Path,
+        writer: CompoundFileWriter,
+    ) -> Result<(), OpenWriteError> {
+        let mut buffer = Vec::new();
+        try!(writer.finalize(&mut buffer).map_err(OpenWriteError::IOError));
+        self.inner
+            .atomic_write(path, &buffer[..])
+            .map_err(OpenWriteError::IOError)
+    }
+
+    /// Opens the compound file at `path` and exposes its components.
+    pub fn read_compound(&self, path: &Path) -> Result<CompoundFile, FileError> {
+        let source = try!(self.inner.open_read(path));
+        CompoundFile::open(source)
+    }
+}
+
+impl ::std::fmt::Debug for CompoundDirectory {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "CompoundDirectory({:?})", self.inner)
+    }
+}
+
+impl Directory for CompoundDirectory {
+    fn open_read(&self, path: &Path) -> Result<ReadOnlySource, FileError> {
+        self.inner.open_read(path)
+    }
+
+    fn delete(&self, path: &Path) -> Result<(), FileError> {
+        self.inner.delete(path)
+    }
+
+    fn list_files(&self) -> io::Result<Vec<::std::path::PathBuf>> {
+        self.inner.list_files()
+    }
+
+    fn open_write(&mut self, path: &Path) -> Result<::directory::WritePtr, OpenWriteError> {
+        self.inner.open_write(path)
+    }
+
+    fn atomic_write(&mut self, path: &Path, data: &[u8]) -> io::Result<()> {
+        self.inner.atomic_write(path, data)
+    }
+
+    fn box_clone(&self) -> Box<Directory> {
+        Box::new(CompoundDirectory {
+            inner: self.inner.box_clone(),
+        })
+    }
+}
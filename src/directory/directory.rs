@@ -1,11 +1,27 @@
 use std::marker::Send;
 use std::fmt;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use directory::error::{FileError, OpenWriteError};
 use directory::{ReadOnlySource, WritePtr};
 use std::result;
-use std::io;
+use std::io::{self, Write};
 use std::marker::Sync;
+use std::collections::HashSet;
+use directory::lock::{DirectoryLock, LockError, lock_contents};
+
+/// Files that `garbage_collect` will never delete, regardless of
+/// whether they appear in the caller's `live_files` set.
+const ALWAYS_LIVE_FILES: [&'static str; 2] = ["meta.json", ".tantivy-writer.lock"];
+
+/// Outcome of a `Directory::garbage_collect` pass.
+#[derive(Debug, Default)]
+pub struct GarbageCollectionResult {
+    /// Files that were successfully deleted.
+    pub deleted: Vec<PathBuf>,
+    /// Files that were supposed to be deleted, but could not be
+    /// (e.g. because another process still has them open).
+    pub failed_to_delete: Vec<PathBuf>,
+}
 
 /// Write-once read many (WORM) abstraction for where tantivy's index should be stored. 
 ///
@@ -31,11 +47,14 @@ pub trait Directory: fmt::Debug + Send + Sync + 'static {
     ///
     /// Removing a file will not affect an eventual
     /// existing ReadOnlySource pointing to it.
-    /// 
+    ///
     /// Removing a nonexistent file, yields a
     /// `FileError::DoesNotExist`.
     fn delete(&self, path: &Path) -> result::Result<(), FileError>;
 
+    /// Lists every file currently present in the directory.
+    fn list_files(&self) -> io::Result<Vec<PathBuf>>;
+
     /// Opens a writer for the *virtual file* associated with 
     /// a Path.
     ///
@@ -65,8 +84,74 @@ pub trait Directory: fmt::Debug + Send + Sync + 'static {
     /// The file may or may not previously exist.
     fn atomic_write(&mut self, path: &Path, data: &[u8]) -> io::Result<()>;
         
-    /// Clones the directory and boxes the clone 
+    /// Clones the directory and boxes the clone
     fn box_clone(&self) -> Box<Directory>;
+
+    /// Deletes every file in the directory that is not in `live_files`.
+    ///
+    /// `meta.json` and the lockfile are always spared, even if the
+    /// caller forgot to include them in `live_files`. Segments that
+    /// have been merged away, or partial files left behind by a
+    /// failed write, are the usual candidates for collection; this is
+    /// typically called after every successful `save_metas`, using the
+    /// files referenced by the freshly written `IndexMeta` as the
+    /// live set.
+    fn garbage_collect(&self, live_files: HashSet<PathBuf>) -> GarbageCollectionResult {
+        let mut result = GarbageCollectionResult::default();
+        let files = match self.list_files() {
+            Ok(files) => files,
+            Err(_) => return result,
+        };
+        for file in files {
+            if live_files.contains(&file) {
+                continue;
+            }
+            let file_name = file.file_name().and_then(|name| name.to_str());
+            if file_name.map_or(false, |name| ALWAYS_LIVE_FILES.contains(&name)) {
+                continue;
+            }
+            match self.delete(&file) {
+                Ok(()) => result.deleted.push(file),
+                Err(_) => result.failed_to_delete.push(file),
+            }
+        }
+        result
+    }
+
+    /// Acquires the lock at `path`, returning `LockError::WouldBlock`
+    /// immediately if it is already held, instead of waiting.
+    ///
+    /// Modeled on Mercurial's `try_with_lock_no_wait`: a lock that
+    /// can't be acquired right away is reported as such rather than
+    /// silently blocking the caller. Returns a `DirectoryLock` RAII
+    /// guard: dropping it releases the lock. `IndexWriter::open` uses
+    /// this in place of the ad-hoc "`open_write` on the lockfile"
+    /// check, so a writer that crashed without releasing its lock can
+    /// be diagnosed (the lockfile records the host and pid that
+    /// created it) rather than wedging every future writer silently.
+    fn acquire_lock(&mut self, path: &Path) -> result::Result<DirectoryLock, LockError> {
+        self.try_acquire_lock(path)
+    }
+
+    /// Attempts to acquire the lock at `path` exactly once, returning
+    /// `LockError::WouldBlock` immediately if it is already held
+    /// instead of waiting.
+    ///
+    /// This is the primitive `acquire_lock` is built on; it exists as
+    /// its own method so a caller that wants the single-attempt
+    /// behavior under a different name (or without going through the
+    /// trait's default `acquire_lock`) still has it available.
+    fn try_acquire_lock(&mut self, path: &Path) -> result::Result<DirectoryLock, LockError> {
+        match self.open_write(path) {
+            Ok(mut write) => {
+                try!(write.write_all(lock_contents().as_bytes()).map_err(LockError::IOError));
+                try!(write.flush().map_err(LockError::IOError));
+                Ok(DirectoryLock::new(self.box_clone(), path.to_owned()))
+            }
+            Err(OpenWriteError::FileAlreadyExists(_)) => Err(LockError::WouldBlock),
+            Err(OpenWriteError::IOError(err)) => Err(LockError::IOError(err)),
+        }
+    }
 }
 
 
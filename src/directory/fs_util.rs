@@ -0,0 +1,50 @@
+use std::path::Path;
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::ffi::CString;
+    use std::io;
+    use std::mem;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use libc;
+
+    // From linux/magic.h. statfs's `f_type` is set to this value for
+    // any NFS mount, regardless of NFS protocol version.
+    const NFS_SUPER_MAGIC: libc::c_long = 0x6969;
+
+    pub fn is_network_fs(path: &Path) -> io::Result<bool> {
+        let c_path = try!(
+            CString::new(path.as_os_str().as_bytes())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+        );
+        unsafe {
+            let mut stat: libc::statfs = mem::zeroed();
+            if libc::statfs(c_path.as_ptr(), &mut stat) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(stat.f_type as libc::c_long == NFS_SUPER_MAGIC)
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use std::io;
+    use std::path::Path;
+
+    pub fn is_network_fs(_path: &Path) -> io::Result<bool> {
+        Ok(false)
+    }
+}
+
+/// Returns whether `path` lives on a network filesystem.
+///
+/// Only NFS, and only on Linux, is currently detected (via the
+/// `statfs` magic number); every other platform or filesystem is
+/// conservatively reported as local. `MmapDirectory::open_read` uses
+/// this to fall back to a plain buffered read instead of mmap-ing
+/// files on mounts where mmap is known to be unreliable.
+pub fn is_network_fs(path: &Path) -> bool {
+    platform::is_network_fs(path).unwrap_or(false)
+}
@@ -0,0 +1,76 @@
+use std::env;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use directory::Directory;
+
+#[cfg(unix)]
+fn current_pid() -> u32 {
+    use libc;
+    unsafe { libc::getpid() as u32 }
+}
+
+#[cfg(not(unix))]
+fn current_pid() -> u32 {
+    0
+}
+
+fn current_host() -> String {
+    env::var("HOSTNAME").unwrap_or_else(|_| "unknown-host".to_string())
+}
+
+/// Contents written to a lockfile, so a lock left behind by a crashed
+/// process can be diagnosed.
+pub fn lock_contents() -> String {
+    format!("{}:{}\n", current_host(), current_pid())
+}
+
+/// Error returned by `Directory::acquire_lock` / `try_acquire_lock`.
+#[derive(Debug)]
+pub enum LockError {
+    /// The lock is already held, by this process or another one.
+    WouldBlock,
+    /// Some other I/O error prevented the lock from being acquired.
+    IOError(io::Error),
+}
+
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LockError::WouldBlock => write!(f, "Lock is already held"),
+            LockError::IOError(ref err) => write!(f, "Failed to acquire lock: {}", err),
+        }
+    }
+}
+
+/// RAII guard over a directory lock, as returned by
+/// `Directory::acquire_lock`.
+///
+/// Dropping the guard deletes the lockfile, releasing the lock. If the
+/// delete fails, the lock is effectively leaked; the lockfile's
+/// contents (host and pid) let an operator tell whether the owning
+/// process is still alive before removing it by hand.
+pub struct DirectoryLock {
+    directory: Box<Directory>,
+    path: PathBuf,
+}
+
+impl DirectoryLock {
+    /// Creates a new `DirectoryLock`.
+    ///
+    /// Callers are expected to have already written the lockfile at
+    /// `path` in `directory` before constructing this guard.
+    pub fn new(directory: Box<Directory>, path: PathBuf) -> DirectoryLock {
+        DirectoryLock {
+            directory: directory,
+            path: path,
+        }
+    }
+}
+
+impl Drop for DirectoryLock {
+    fn drop(&mut self) {
+        let _ = self.directory.delete(&self.path);
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use memmap::{Mmap, Protection};
+use tempdir::TempDir;
+
+use directory::{Directory, ReadOnlySource, WritePtr};
+use directory::error::{FileError, OpenWriteError};
+use directory::fs_util::is_network_fs;
+
+/// Retains the `Mmap` of every file this directory has mapped, keyed
+/// by path, until that path is deleted or rewritten.
+///
+/// Every `ReadOnlySource` built from a mapping holds its own
+/// `Arc<Mmap>`, so when `delete` or `atomic_write` drops a path's
+/// cache entry, the mapping (and the data behind those sources) stays
+/// valid until the last of them is dropped. This is how the
+/// `Directory` trait's promise that deleting a file never affects an
+/// existing `ReadOnlySource` is kept.
+struct MmapCache {
+    cache: HashMap<PathBuf, Arc<Mmap>>,
+}
+
+impl MmapCache {
+    fn new() -> MmapCache {
+        MmapCache { cache: HashMap::new() }
+    }
+
+    fn get_mmap(&mut self, full_path: &Path) -> io::Result<Arc<Mmap>> {
+        if let Some(mmap) = self.cache.get(full_path) {
+            return Ok(mmap.clone());
+        }
+        let mmap = Arc::new(try!(Mmap::open_path(full_path, Protection::Read)));
+        self.cache.insert(full_path.to_owned(), mmap.clone());
+        Ok(mmap)
+    }
+
+    fn forget(&mut self, full_path: &Path) {
+        self.cache.remove(full_path);
+    }
+}
+
+/// Directory backed by the filesystem, reading files through a memory
+/// map.
+///
+/// On a network filesystem (currently: NFS on Linux, detected via
+/// `fs_util::is_network_fs`), `open_read` falls back to a plain
+/// buffered read instead of mmap-ing, since mmap over NFS is known to
+/// be unreliable (SIGBUS on a server-side truncate or eviction).
+pub struct MmapDirectory {
+    root_path: PathBuf,
+    mmap_cache: Arc<RwLock<MmapCache>>,
+    is_network_fs: bool,
+    _temp_dir: Option<Arc<TempDir>>,
+}
+
+impl MmapDirectory {
+    /// Opens a `MmapDirectory` at `root_path`, which must already
+    /// exist.
+    pub fn open(root_path: &Path) -> Result<MmapDirectory, OpenDirectoryError> {
+        if !root_path.exists() {
+            return Err(OpenDirectoryError::DoesNotExist(root_path.to_owned()));
+        }
+        if !root_path.is_dir() {
+            return Err(OpenDirectoryError::NotADirectory(root_path.to_owned()));
+        }
+        Ok(MmapDirectory {
+            root_path: root_path.to_owned(),
+            mmap_cache: Arc::new(RwLock::new(MmapCache::new())),
+            is_network_fs: is_network_fs(root_path),
+            _temp_dir: None,
+        })
+    }
+
+    /// Creates a `MmapDirectory` in a freshly created temporary
+    /// directory, which is deleted once every clone of the returned
+    /// `MmapDirectory` has been dropped.
+    pub fn create_from_tempdir() -> io::Result<MmapDirectory> {
+        let tempdir = try!(TempDir::new("tantivy"));
+        let root_path = tempdir.path().to_owned();
+        Ok(MmapDirectory {
+            root_path: root_path.clone(),
+            mmap_cache: Arc::new(RwLock::new(MmapCache::new())),
+            is_network_fs: is_network_fs(&root_path),
+            _temp_dir: Some(Arc::new(tempdir)),
+        })
+    }
+
+    fn resolve_path(&self, path: &Path) -> PathBuf {
+        self.root_path.join(path)
+    }
+}
+
+impl Directory for MmapDirectory {
+    fn open_read(&self, path: &Path) -> Result<ReadOnlySource, FileError> {
+        let full_path = self.resolve_path(path);
+        if !full_path.exists() {
+            return Err(FileError::DoesNotExist(path.to_owned()));
+        }
+        if self.is_network_fs {
+            let mut file = try!(File::open(&full_path).map_err(FileError::IOError));
+            let mut buffer = Vec::new();
+            try!(file.read_to_end(&mut buffer).map_err(FileError::IOError));
+            return Ok(ReadOnlySource::from(buffer));
+        }
+        // A zero-length file cannot be memory mapped.
+        if try!(fs::metadata(&full_path).map_err(FileError::IOError)).len() == 0 {
+            return Ok(ReadOnlySource::from(Vec::new()));
+        }
+        let mmap = try!(
+            self.mmap_cache
+                .write()
+                .expect("Mmap cache lock poisoned")
+                .get_mmap(&full_path)
+                .map_err(FileError::IOError)
+        );
+        Ok(ReadOnlySource::from(mmap))
+    }
+
+    fn delete(&self, path: &Path) -> Result<(), FileError> {
+        let full_path = self.resolve_path(path);
+        try!(fs::remove_file(&full_path).map_err(|err| {
+            if err.kind() == io::ErrorKind::NotFound {
+                FileError::DoesNotExist(path.to_owned())
+            } else {
+                FileError::IOError(err)
+            }
+        }));
+        // Only the cache entry goes away here; any `Arc<Mmap>` already
+        // handed out in a `ReadOnlySource` keeps the mapping alive
+        // until that source is itself dropped.
+        self.mmap_cache
+            .write()
+            .expect("Mmap cache lock poisoned")
+            .forget(&full_path);
+        Ok(())
+    }
+
+    fn list_files(&self) -> io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for entry in try!(fs::read_dir(&self.root_path)) {
+            let entry = try!(entry);
+            if entry.path().is_file() {
+                files.push(PathBuf::from(entry.file_name()));
+            }
+        }
+        Ok(files)
+    }
+
+    fn open_write(&mut self, path: &Path) -> Result<WritePtr, OpenWriteError> {
+        let full_path = self.resolve_path(path);
+        let file = try!(
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&full_path)
+                .map_err(|err| {
+                    if err.kind() == io::ErrorKind::AlreadyExists {
+                        OpenWriteError::FileAlreadyExists(path.to_owned())
+                    } else {
+                        OpenWriteError::IOError(err)
+                    }
+                })
+        );
+        Ok(Box::new(file))
+    }
+
+    fn atomic_write(&mut self, path: &Path, data: &[u8]) -> io::Result<()> {
+        let full_path = self.resolve_path(path);
+        let tmp_path = full_path.with_extension("tmp");
+        {
+            let mut tmp_file = try!(File::create(&tmp_path));
+            try!(tmp_file.write_all(data));
+            try!(tmp_file.flush());
+        }
+        try!(fs::rename(&tmp_path, &full_path));
+        // The cached mapping, if any, is of the replaced file.
+        self.mmap_cache
+            .write()
+            .expect("Mmap cache lock poisoned")
+            .forget(&full_path);
+        Ok(())
+    }
+
+    fn box_clone(&self) -> Box<Directory> {
+        Box::new(MmapDirectory {
+            root_path: self.root_path.clone(),
+            mmap_cache: self.mmap_cache.clone(),
+            is_network_fs: self.is_network_fs,
+            _temp_dir: self._temp_dir.clone(),
+        })
+    }
+}
+
+impl ::std::fmt::Debug for MmapDirectory {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "MmapDirectory({:?})", self.root_path)
+    }
+}
+
+/// Error returned by `MmapDirectory::open`.
+#[derive(Debug)]
+pub enum OpenDirectoryError {
+    /// The given path does not exist.
+    DoesNotExist(PathBuf),
+    /// The given path exists, but is not a directory.
+    NotADirectory(PathBuf),
+}
+
+impl ::std::fmt::Display for OpenDirectoryError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match *self {
+            OpenDirectoryError::DoesNotExist(ref path) => {
+                write!(f, "Directory {:?} does not exist", path)
+            }
+            OpenDirectoryError::NotADirectory(ref path) => {
+                write!(f, "{:?} is not a directory", path)
+            }
+        }
+    }
+}
+
+impl From<OpenDirectoryError> for ::Error {
+    fn from(err: OpenDirectoryError) -> ::Error {
+        match err {
+            OpenDirectoryError::DoesNotExist(path) => ::Error::PathDoesNotExist(path),
+            OpenDirectoryError::NotADirectory(path) => ::Error::PathDoesNotExist(path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::io::Write;
+    use directory::Directory;
+
+    #[test]
+    fn test_delete_keeps_existing_read_only_source_readable() {
+        let mut directory = MmapDirectory::create_from_tempdir().unwrap();
+        let path = Path::new("test_file");
+        {
+            let mut write = directory.open_write(path).unwrap();
+            write.write_all(&[1, 2, 3, 4]).unwrap();
+            write.flush().unwrap();
+        }
+        let source = directory.open_read(path).unwrap();
+        directory.delete(path).unwrap();
+        assert_eq!(&*source, &[1u8, 2, 3, 4]);
+    }
+}
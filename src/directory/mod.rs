@@ -3,6 +3,9 @@ mod ram_directory;
 mod directory;
 mod read_only_source;
 mod shared_vec_slice;
+mod compound_directory;
+mod fs_util;
+mod lock;
 
 /// Errors specific to the directory module.
 pub mod error;
@@ -10,9 +13,11 @@ pub mod error;
 use std::io::{Seek, Write};
 
 pub use self::read_only_source::ReadOnlySource;
-pub use self::directory::Directory;
+pub use self::directory::{Directory, GarbageCollectionResult};
 pub use self::ram_directory::RAMDirectory;
 pub use self::mmap_directory::MmapDirectory;
+pub use self::compound_directory::{CompoundDirectory, CompoundFile, CompoundFileWriter, SegmentComponent};
+pub use self::lock::{DirectoryLock, LockError};
 
 /// Synonym of Seek + Write
 pub trait SeekableWrite: Seek + Write {}
@@ -0,0 +1,100 @@
+use std::sync::{Arc, Mutex};
+
+use schema::Term;
+use core::stamp::Opstamp;
+
+/// A single delete operation: remove every document indexed under
+/// `term`.
+#[derive(Clone, Debug)]
+pub struct DeleteOperation {
+    pub opstamp: Opstamp,
+    pub term: Term,
+}
+
+struct DeleteQueueInner {
+    operations: Vec<DeleteOperation>,
+}
+
+/// Shared, append-only log of delete operations.
+///
+/// `IndexWriter::delete_term` pushes onto the queue. Each segment
+/// carries its own `DeleteCursor`, obtained with `cursor()`, which
+/// remembers how far that segment has already advanced so
+/// `advance_deletes` only ever looks at the operations it hasn't
+/// applied yet.
+#[derive(Clone)]
+pub struct DeleteQueue {
+    inner: Arc<Mutex<DeleteQueueInner>>,
+}
+
+impl DeleteQueue {
+    /// Creates a new, empty `DeleteQueue`.
+    pub fn new() -> DeleteQueue {
+        DeleteQueue {
+            inner: Arc::new(Mutex::new(DeleteQueueInner {
+                operations: Vec::new(),
+            })),
+        }
+    }
+
+    /// Appends a delete operation to the queue.
+    pub fn push(&self, opstamp: Opstamp, term: Term) {
+        let mut inner = self.inner.lock().expect("DeleteQueue lock poisoned");
+        inner.operations.push(DeleteOperation {
+            opstamp: opstamp,
+            term: term,
+        });
+    }
+
+    /// Returns a cursor starting at the current end of the queue.
+    ///
+    /// A newly created segment should get its cursor right after it
+    /// starts accepting documents, so it only ever needs to apply
+    /// deletes recorded after that point.
+    pub fn cursor(&self) -> DeleteCursor {
+        let position = self.inner.lock().expect("DeleteQueue lock poisoned").operations.len();
+        DeleteCursor {
+            queue: self.clone(),
+            position: position,
+        }
+    }
+}
+
+/// Tracks how far a given segment has applied the operations recorded
+/// in a `DeleteQueue`.
+#[derive(Clone)]
+pub struct DeleteCursor {
+    queue: DeleteQueue,
+    position: usize,
+}
+
+impl DeleteCursor {
+    /// Returns the delete operations that have not been applied yet,
+    /// advancing the cursor past them.
+    pub fn drain(&mut self) -> Vec<DeleteOperation> {
+        let inner = self.queue.inner.lock().expect("DeleteQueue lock poisoned");
+        let pending = inner.operations[self.position..].to_vec();
+        self.position = inner.operations.len();
+        pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use schema::{Term, Field};
+
+    #[test]
+    fn test_delete_cursor_only_sees_future_operations() {
+        let queue = DeleteQueue::new();
+        let field = Field(0);
+        queue.push(1, Term::from_field_text(field, "a"));
+        let mut cursor = queue.cursor();
+        queue.push(2, Term::from_field_text(field, "b"));
+        let pending = cursor.drain();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].opstamp, 2);
+        assert!(cursor.drain().is_empty());
+    }
+}
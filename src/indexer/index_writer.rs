@@ -0,0 +1,113 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use Result;
+use Error;
+use core::{Index, SegmentId};
+use core::stamp::Opstamp;
+use directory::{DirectoryLock, LockError};
+use indexer::delete_queue::{DeleteCursor, DeleteQueue};
+use schema::Term;
+
+lazy_static! {
+    static ref LOCKFILE_PATH: PathBuf = PathBuf::from(".tantivy-writer.lock");
+}
+
+/// Deletes documents from an `Index` and commits those deletes. It has
+/// no method for adding documents.
+///
+/// Only one `IndexWriter` may be open on a given directory at a time:
+/// `open` acquires the directory's lock via `Directory::acquire_lock`
+/// and holds it for as long as the `IndexWriter` lives, releasing it
+/// on `Drop`.
+pub struct IndexWriter {
+    index: Index,
+    _lock: DirectoryLock,
+    delete_queue: DeleteQueue,
+    segment_cursors: Mutex<HashMap<SegmentId, DeleteCursor>>,
+    #[allow(dead_code)]
+    num_threads: usize,
+    #[allow(dead_code)]
+    heap_size_in_bytes: usize,
+}
+
+impl IndexWriter {
+    /// Opens a writer over `index`.
+    ///
+    /// # Errors
+    /// If another `IndexWriter` already holds the lock, returns
+    /// `Error::FileAlreadyExists`.
+    pub fn open(index: &Index, num_threads: usize, heap_size_in_bytes: usize) -> Result<IndexWriter> {
+        let mut directory = index.directory().box_clone();
+        let lock = try!(directory.acquire_lock(&LOCKFILE_PATH).map_err(|lock_err| match lock_err {
+            LockError::WouldBlock => Error::FileAlreadyExists(LOCKFILE_PATH.clone()),
+            LockError::IOError(io_err) => Error::from(io_err),
+        }));
+        Ok(IndexWriter {
+            index: index.clone(),
+            _lock: lock,
+            delete_queue: index.delete_queue(),
+            segment_cursors: Mutex::new(HashMap::new()),
+            num_threads: num_threads,
+            heap_size_in_bytes: heap_size_in_bytes,
+        })
+    }
+
+    /// Returns the `DeleteCursor` `segment_id` should use, creating
+    /// one at the delete queue's current position the first time this
+    /// segment is seen.
+    ///
+    /// A segment never needs to apply deletes recorded before it
+    /// started accepting documents, so handing out a fresh cursor the
+    /// first time a segment is encountered is exactly the "wiring at
+    /// segment creation" the queue's `cursor()` doc comment describes.
+    fn cursor_for_segment(&self, segment_id: SegmentId) -> DeleteCursor {
+        let mut segment_cursors = self.segment_cursors.lock().expect("segment cursors lock poisoned");
+        segment_cursors
+            .entry(segment_id)
+            .or_insert_with(|| self.delete_queue.cursor())
+            .clone()
+    }
+
+    /// Deletes every document indexed under `term`.
+    ///
+    /// The deletion only becomes visible to searchers after the next
+    /// `commit`.
+    pub fn delete_term(&self, term: Term) -> Opstamp {
+        let opstamp = self.index.new_opstamp();
+        self.delete_queue.push(opstamp, term);
+        opstamp
+    }
+
+    /// Commits every delete recorded so far.
+    ///
+    /// Applies the pending delete operations to every currently
+    /// searchable segment's `AliveBitSet` and returns the opstamp that
+    /// was committed.
+    pub fn commit(&mut self) -> Result<Opstamp> {
+        let commit_opstamp = self.index.new_opstamp();
+        let segment_metas = try!(self.index.segment_metas());
+
+        let live_segment_ids: HashSet<SegmentId> =
+            segment_metas.iter().map(|segment_meta| segment_meta.id()).collect();
+        self.segment_cursors
+            .lock()
+            .expect("segment cursors lock poisoned")
+            .retain(|segment_id, _| live_segment_ids.contains(segment_id));
+
+        let mut updated_metas = Vec::with_capacity(segment_metas.len());
+        for segment_meta in segment_metas {
+            let mut cursor = self.cursor_for_segment(segment_meta.id());
+            let updated_meta = try!(self.index.advance_deletes(&segment_meta, &mut cursor, commit_opstamp));
+            self.segment_cursors
+                .lock()
+                .expect("segment cursors lock poisoned")
+                .insert(segment_meta.id(), cursor);
+            updated_metas.push(updated_meta);
+        }
+
+        try!(self.index.replace_segment_metas(updated_metas, commit_opstamp));
+        Ok(commit_opstamp)
+    }
+}
@@ -0,0 +1,6 @@
+mod index_writer;
+pub mod delete_queue;
+mod merger;
+pub mod segment_updater;
+
+pub use self::index_writer::IndexWriter;
@@ -0,0 +1,120 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc;
+use std::thread;
+
+use core::{Index, SegmentId, SegmentReader};
+use core::merge_policy::{MergePolicy, MergeCandidate, SegmentMeta};
+use indexer::merger::IndexMerger;
+
+const NUM_MERGE_THREADS: usize = 4;
+
+trait FnBox {
+    fn call_box(self: Box<Self>);
+}
+
+impl<F: FnOnce()> FnBox for F {
+    fn call_box(self: Box<F>) {
+        (*self)()
+    }
+}
+
+type Job = Box<FnBox + Send>;
+
+/// Drives background merges.
+///
+/// After every `Index::publish_segments`, the `Index` asks its
+/// `SegmentUpdater` to look at the current segments and schedule
+/// whatever the `MergePolicy` recommends. Merges run on a small,
+/// dedicated thread pool so they never block the calling thread, and
+/// `merging_segments` makes sure a given `SegmentId` is never handed
+/// out to two in-flight merges at once.
+pub struct SegmentUpdater {
+    merge_policy: Box<MergePolicy>,
+    merging_segments: Arc<Mutex<HashSet<SegmentId>>>,
+    job_sender: mpsc::Sender<Job>,
+}
+
+impl SegmentUpdater {
+    /// Creates a `SegmentUpdater` and starts its merge thread pool.
+    pub fn new(merge_policy: Box<MergePolicy>) -> SegmentUpdater {
+        let (job_sender, job_receiver) = mpsc::channel::<Job>();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        for _ in 0..NUM_MERGE_THREADS {
+            let job_receiver = job_receiver.clone();
+            thread::spawn(move || loop {
+                let job = {
+                    let job_receiver = job_receiver.lock().expect("Merge job queue lock poisoned");
+                    job_receiver.recv()
+                };
+                match job {
+                    Ok(job) => job.call_box(),
+                    Err(_) => break,
+                }
+            });
+        }
+        SegmentUpdater {
+            merge_policy: merge_policy,
+            merging_segments: Arc::new(Mutex::new(HashSet::new())),
+            job_sender: job_sender,
+        }
+    }
+
+    /// Asks the merge policy for candidates given the current segment
+    /// metas, and schedules each one that is not already in flight.
+    pub fn consider_merges(&self, index: &Index, segments: &[SegmentMeta]) {
+        let candidates = self.merge_policy.compute_merge_candidates(segments);
+        for candidate in candidates {
+            self.schedule_merge(index, candidate);
+        }
+    }
+
+    fn schedule_merge(&self, index: &Index, candidate: MergeCandidate) {
+        {
+            let mut merging_segments = self.merging_segments
+                .lock()
+                .expect("Merging segments lock poisoned");
+            if candidate.iter().any(|segment_id| merging_segments.contains(segment_id)) {
+                return;
+            }
+            for segment_id in &candidate {
+                merging_segments.insert(*segment_id);
+            }
+        }
+        let index = index.clone();
+        let merging_segments = self.merging_segments.clone();
+        let _ = self.job_sender.send(Box::new(move || {
+            let mut index = index;
+            if let Err(err) = merge_segments(&mut index, &candidate[..]) {
+                error!("Merge of {:?} failed: {:?}", candidate, err);
+            }
+            let mut merging_segments = merging_segments
+                .lock()
+                .expect("Merging segments lock poisoned");
+            for segment_id in &candidate {
+                merging_segments.remove(segment_id);
+            }
+        }));
+    }
+}
+
+/// Merges `segment_ids` into a single new segment and publishes it in
+/// place of its inputs.
+///
+/// Source segments are opened as `SegmentReader`s, not raw `Segment`s,
+/// so the merger can see each one's `AliveBitSet` and skip documents
+/// that were deleted before the merge ran, instead of resurrecting
+/// them into the merged segment.
+fn merge_segments(index: &mut Index, segment_ids: &[SegmentId]) -> ::Result<()> {
+    let segment_readers: Vec<SegmentReader> = try!(
+        segment_ids
+            .iter()
+            .map(|&segment_id| SegmentReader::open(index.segment(segment_id)))
+            .collect()
+    );
+    let merged_segment = index.new_segment();
+    let merger = try!(IndexMerger::open(index.schema(), &segment_readers[..]));
+    try!(merger.write(merged_segment.clone()));
+    let merged_segment_ids: HashSet<SegmentId> = segment_ids.iter().cloned().collect();
+    index.publish_merge_segment(merged_segment_ids, merged_segment.id())
+}